@@ -0,0 +1,371 @@
+//! The events task: owns the gateway connection, keeps it alive with a
+//! [`Heartbeat`], keeps a [`ReadyEvent`] snapshot reconciled against it
+//! across reconnects, and winds down cleanly — typing sessions ended,
+//! in-flight sends awaited — when told to via [`shutdown::Shutdown`].
+//!
+//! This crate is deliberately transport-agnostic: [`Gateway`] is the only
+//! thing that knows how to open a websocket and move frames across it, so
+//! the reconnect/backoff loop here can be driven by a fake in tests without
+//! pulling in a websocket client.
+
+pub mod shutdown;
+pub mod typing;
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use robespierre_models::{
+    events::{ClientToServerEvent, ConnectionState, Heartbeat, ReadyEvent, ServerToClientEvent},
+    id::ChannelId,
+};
+use tokio::sync::{mpsc, watch};
+
+use shutdown::Shutdown;
+use typing::TypingSession;
+
+/// Opens and exchanges frames with the gateway. Implemented by the websocket
+/// transport; [`run`] only knows how to drive it through the authenticate /
+/// receive / reconnect lifecycle.
+#[async_trait::async_trait]
+pub trait Gateway: Send {
+    /// Opens a fresh connection and sends `auth` as the first frame.
+    async fn connect(&mut self, auth: &ClientToServerEvent) -> std::io::Result<()>;
+    /// Sends a frame over the current connection.
+    async fn send(&mut self, event: ClientToServerEvent) -> std::io::Result<()>;
+    /// Receives the next frame. `Ok(None)` means the connection closed
+    /// cleanly; both that and an `Err` trigger a reconnect.
+    async fn recv(&mut self) -> std::io::Result<Option<ServerToClientEvent>>;
+}
+
+/// The exponential backoff schedule between reconnect attempts.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub multiplier: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(500),
+            max: Duration::from_secs(60),
+            multiplier: 2,
+        }
+    }
+}
+
+impl Backoff {
+    fn next(self, current: Duration) -> Duration {
+        std::cmp::min(current * self.multiplier, self.max)
+    }
+}
+
+/// How often to send a [`ClientToServerEvent::Ping`], and how long to wait
+/// for the matching [`ServerToClientEvent::Pong`] before treating the
+/// connection as dead and reconnecting.
+#[derive(Debug, Clone, Copy)]
+pub struct HeartbeatConfig {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(15),
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// The latest heartbeat round-trip latency, shared between the events task
+/// and whatever hands out [`Context::latency`](../robespierre/struct.Context.html#method.latency)
+/// to callers.
+pub type Latency = Arc<RwLock<Option<Duration>>>;
+
+/// Everything `robespierre::Context` needs in order to reflect a `run` task
+/// it didn't start itself: the connection state and latency `run` publishes,
+/// a way to send frames (so [`Handle::start_typing`] can drive a
+/// [`TypingSession`]), and the [`Shutdown`] token that task will stop on.
+#[derive(Clone)]
+pub struct Handle {
+    frames: mpsc::UnboundedSender<ClientToServerEvent>,
+    state: watch::Receiver<ConnectionState>,
+    latency: Latency,
+    shutdown: Shutdown,
+    typing_resend_interval: Duration,
+    typing: typing::Registry,
+}
+
+impl Handle {
+    pub fn connection_state(&self) -> ConnectionState {
+        *self.state.borrow()
+    }
+
+    pub fn latency(&self) -> Option<Duration> {
+        *self.latency.read().unwrap()
+    }
+
+    pub fn shutdown(&self) -> &Shutdown {
+        &self.shutdown
+    }
+
+    pub fn start_typing(&self, channel: ChannelId) -> TypingSession {
+        TypingSession::start(
+            channel,
+            self.frames.clone(),
+            self.typing.clone(),
+            self.typing_resend_interval,
+        )
+    }
+}
+
+/// How often a live [`TypingSession`] resends `BeginTyping`, matching the
+/// ~8s window Revolt stops showing a typing indicator after.
+const DEFAULT_TYPING_RESEND_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Spawns [`run`] on the current Tokio runtime and returns its `JoinHandle`
+/// together with the [`Handle`] a `robespierre::Context` attaches via
+/// `Context::with_events` to observe it. The [`Shutdown`] token that stops
+/// it is reached through [`Handle::shutdown`].
+pub fn spawn<G>(
+    gateway: G,
+    auth: ClientToServerEvent,
+    backoff: Backoff,
+    heartbeat: HeartbeatConfig,
+    on_event: impl FnMut(ServerToClientEvent) + Send + 'static,
+) -> (tokio::task::JoinHandle<std::io::Result<()>>, Handle)
+where
+    G: Gateway + Send + 'static,
+{
+    let (state_tx, state_rx) = watch::channel(ConnectionState::Connecting);
+    let latency: Latency = Arc::new(RwLock::new(None));
+    let (frames_tx, frames_rx) = mpsc::unbounded_channel();
+    let shutdown = Shutdown::new();
+    let typing = typing::Registry::default();
+
+    let handle = Handle {
+        frames: frames_tx,
+        state: state_rx,
+        latency: latency.clone(),
+        shutdown: shutdown.clone(),
+        typing_resend_interval: DEFAULT_TYPING_RESEND_INTERVAL,
+        typing: typing.clone(),
+    };
+
+    let join = tokio::spawn(run(
+        gateway, auth, backoff, heartbeat, state_tx, latency, frames_rx, shutdown, typing, on_event,
+    ));
+
+    (join, handle)
+}
+
+/// The `ServerToClientEvent` variant name, for tagging the span and counter
+/// `dispatch` records per frame without cloning or printing the payload.
+#[cfg(feature = "metrics")]
+fn variant_name(event: &ServerToClientEvent) -> &'static str {
+    match event {
+        ServerToClientEvent::Error { .. } => "Error",
+        ServerToClientEvent::Authenticated => "Authenticated",
+        ServerToClientEvent::Pong { .. } => "Pong",
+        ServerToClientEvent::Ready { .. } => "Ready",
+        ServerToClientEvent::Message { .. } => "Message",
+        ServerToClientEvent::MessageUpdate { .. } => "MessageUpdate",
+        ServerToClientEvent::MessageDelete { .. } => "MessageDelete",
+        ServerToClientEvent::ChannelCreate { .. } => "ChannelCreate",
+        ServerToClientEvent::ChannelUpdate { .. } => "ChannelUpdate",
+        ServerToClientEvent::ChannelDelete { .. } => "ChannelDelete",
+        ServerToClientEvent::ChannelGroupJoin { .. } => "ChannelGroupJoin",
+        ServerToClientEvent::ChannelGroupLeave { .. } => "ChannelGroupLeave",
+        ServerToClientEvent::ChannelStartTyping { .. } => "ChannelStartTyping",
+        ServerToClientEvent::ChannelStopTyping { .. } => "ChannelStopTyping",
+        ServerToClientEvent::ChannelAck { .. } => "ChannelAck",
+        ServerToClientEvent::ServerUpdate { .. } => "ServerUpdate",
+        ServerToClientEvent::ServerDelete { .. } => "ServerDelete",
+        ServerToClientEvent::ServerMemberUpdate { .. } => "ServerMemberUpdate",
+        ServerToClientEvent::ServerMemberJoin { .. } => "ServerMemberJoin",
+        ServerToClientEvent::ServerMemberLeave { .. } => "ServerMemberLeave",
+        ServerToClientEvent::ServerRoleUpdate { .. } => "ServerRoleUpdate",
+        ServerToClientEvent::ServerRoleDelete { .. } => "ServerRoleDelete",
+        ServerToClientEvent::UserUpdate { .. } => "UserUpdate",
+        ServerToClientEvent::UserRelationship { .. } => "UserRelationship",
+    }
+}
+
+/// Spans and counts the dispatch of `event` before handing it to `on_event`,
+/// tagged with the variant name so each kind of frame gets its own span and
+/// `robespierre_events_dispatched_total` series.
+fn dispatch(event: ServerToClientEvent, on_event: &mut impl FnMut(ServerToClientEvent)) {
+    #[cfg(feature = "metrics")]
+    {
+        let variant = variant_name(&event);
+        let _span = tracing::info_span!("dispatch_event", event = variant).entered();
+        metrics::counter!("robespierre_events_dispatched_total", "event" => variant).increment(1);
+        on_event(event);
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    on_event(event);
+}
+
+/// Runs the events task until `shutdown` is triggered (returning `Ok`), or
+/// until the gateway fails in a way it doesn't retry (returning the `Err`):
+/// the initial `connect` call fails, or a connection closes or errors before
+/// sending its `Ready`. Failed reconnect attempts are retried indefinitely.
+///
+/// Authenticates, waits for the initial `Ready`, then relays every frame to
+/// `on_event` while sending a [`ClientToServerEvent::Ping`] every
+/// `heartbeat.interval` and recording the round trip in `latency`. When the
+/// connection drops — because the socket closed, a `Pong` didn't arrive
+/// within `heartbeat.timeout`, or the heartbeat round trip otherwise stalls —
+/// it reconnects with `backoff`, re-authenticates, and diffs the fresh
+/// `Ready` against the last one seen via [`ReadyEvent::reconcile`], replaying
+/// the synthesized events through `on_event` so a cache built from the event
+/// stream doesn't need a full rebuild. `state` is updated with each
+/// [`ConnectionState`] transition so a host application can observe
+/// reconnects.
+pub async fn run<G>(
+    mut gateway: G,
+    auth: ClientToServerEvent,
+    backoff: Backoff,
+    heartbeat: HeartbeatConfig,
+    state: watch::Sender<ConnectionState>,
+    latency: Latency,
+    mut frames: mpsc::UnboundedReceiver<ClientToServerEvent>,
+    shutdown: Shutdown,
+    typing: typing::Registry,
+    mut on_event: impl FnMut(ServerToClientEvent) + Send,
+) -> std::io::Result<()>
+where
+    G: Gateway,
+{
+    let _ = state.send(ConnectionState::Connecting);
+    gateway.connect(&auth).await?;
+    let mut ready = await_ready(&mut gateway, &mut on_event).await?;
+    let _ = state.send(ConnectionState::Connected);
+
+    let mut pings = Heartbeat::new();
+    // Whether a `Ping` is still waiting on its `Pong`. No new ping is sent
+    // while one is, since `Heartbeat::ping` restarts the round-trip clock and
+    // would keep a missed `Pong` from ever outliving `heartbeat.timeout`.
+    let mut awaiting_pong = false;
+    let mut ticker = tokio::time::interval(heartbeat.interval);
+    ticker.tick().await; // the first tick fires immediately; send on a real interval instead
+
+    loop {
+        tokio::select! {
+            _ = shutdown.triggered() => {
+                // Every live `TypingSession` gets its final `EndTyping` sent
+                // here rather than left to `Drop`, so a session the caller
+                // is still holding doesn't leave the indicator stuck on.
+                for channel in typing.drain() {
+                    let _ = gateway.send(ClientToServerEvent::EndTyping { channel }).await;
+                }
+                // Wait for every outstanding `ShutdownGuard` (e.g. an
+                // in-flight `send_message`) before giving up the socket.
+                shutdown.drain().await;
+                return Ok(());
+            }
+            _ = ticker.tick() => {
+                let dead = if awaiting_pong {
+                    pings.is_dead(heartbeat.timeout)
+                } else {
+                    awaiting_pong = gateway.send(pings.ping()).await.is_ok();
+                    !awaiting_pong
+                };
+                if dead {
+                    *latency.write().unwrap() = None;
+                    reconnect(&mut gateway, &auth, backoff, &state, &mut ready, &mut on_event).await?;
+                    pings = Heartbeat::new();
+                    awaiting_pong = false;
+                }
+            }
+            frame = frames.recv() => {
+                // `None` means every `Handle` (and so every `TypingSession`)
+                // was dropped; nothing left to forward, but `run` itself
+                // keeps going until `shutdown` says otherwise.
+                if let Some(frame) = frame {
+                    let _ = gateway.send(frame).await;
+                }
+            }
+            received = gateway.recv() => {
+                match received {
+                    Ok(Some(ServerToClientEvent::Pong { time })) => {
+                        if let Some(rtt) = pings.pong(time) {
+                            *latency.write().unwrap() = Some(rtt);
+                            awaiting_pong = false;
+                        }
+                        dispatch(ServerToClientEvent::Pong { time }, &mut on_event);
+                    }
+                    Ok(Some(event)) => dispatch(event, &mut on_event),
+                    Ok(None) | Err(_) => {
+                        *latency.write().unwrap() = None;
+                        reconnect(&mut gateway, &auth, backoff, &state, &mut ready, &mut on_event).await?;
+                        pings = Heartbeat::new();
+                        awaiting_pong = false;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Reconnects `gateway` with `backoff`, then reconciles `ready` against the
+/// fresh `Ready` it receives, replaying the synthesized events through
+/// `on_event` and updating `ready` in place.
+async fn reconnect<G: Gateway>(
+    gateway: &mut G,
+    auth: &ClientToServerEvent,
+    backoff: Backoff,
+    state: &watch::Sender<ConnectionState>,
+    ready: &mut ReadyEvent,
+    on_event: &mut impl FnMut(ServerToClientEvent),
+) -> std::io::Result<()> {
+    let _ = state.send(ConnectionState::Reconnecting);
+    let mut delay = backoff.initial;
+    loop {
+        tokio::time::sleep(delay).await;
+        if gateway.connect(auth).await.is_ok() {
+            break;
+        }
+        delay = backoff.next(delay);
+    }
+
+    let _ = state.send(ConnectionState::Resyncing);
+    let fresh = await_ready(gateway, on_event).await?;
+    for event in ReadyEvent::reconcile(ready, &fresh) {
+        dispatch(event, on_event);
+    }
+    *ready = fresh;
+    let _ = state.send(ConnectionState::Connected);
+
+    Ok(())
+}
+
+/// Receives frames until the `Ready` that every connection starts with
+/// arrives, passing along anything else seen in between (the gateway
+/// contract is that `Ready` comes first, but this doesn't assume it's the
+/// *only* thing in the first frame).
+async fn await_ready<G: Gateway>(
+    gateway: &mut G,
+    on_event: &mut impl FnMut(ServerToClientEvent),
+) -> std::io::Result<ReadyEvent> {
+    loop {
+        match gateway.recv().await? {
+            Some(ServerToClientEvent::Ready { event }) => {
+                #[cfg(feature = "metrics")]
+                metrics::counter!("robespierre_events_dispatched_total", "event" => "Ready")
+                    .increment(1);
+                return Ok(event);
+            }
+            Some(other) => dispatch(other, on_event),
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionReset,
+                    "gateway closed before sending Ready",
+                ))
+            }
+        }
+    }
+}
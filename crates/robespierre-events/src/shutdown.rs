@@ -0,0 +1,105 @@
+//! Cooperative shutdown for the events loop and anything else that holds a
+//! live connection open (typing sessions, in-flight sends) and needs a
+//! chance to wind down cleanly instead of being dropped mid-flight.
+
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+use tokio::sync::Notify;
+
+/// A clone-able cancellation signal. Cloning shares the same underlying
+/// signal; calling [`Shutdown::trigger`] on any clone wakes every task
+/// awaiting [`Shutdown::triggered`] on any other clone.
+///
+/// `robespierre_events::run` races this against its next read from the
+/// socket (`tokio::select! { _ = shutdown.triggered() => ..., msg =
+/// gateway.recv() => ... }`) so it stops accepting new work but can still
+/// drain what's in flight: every live `TypingSession` (from
+/// `ChannelIdExt::start_typing`) gets sent a final `EndTyping` before the
+/// loop returns, via the same registry `TypingSession::drop` uses, and
+/// [`Shutdown::drain`] waits for every outstanding [`ShutdownGuard`] — one
+/// is held for the duration of each in-flight `send_message` — before the
+/// socket closes.
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown {
+    notify: Arc<Notify>,
+    triggered: Arc<AtomicBool>,
+    outstanding: Arc<AtomicUsize>,
+    drained: Arc<Notify>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Signals every clone of this handle to begin shutting down. Idempotent.
+    pub fn trigger(&self) {
+        self.triggered.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    /// Whether `trigger` has already been called.
+    pub fn is_triggered(&self) -> bool {
+        self.triggered.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once `trigger` has been called, immediately if it already
+    /// has.
+    pub async fn triggered(&self) {
+        if self.is_triggered() {
+            return;
+        }
+
+        // Register for the notification *before* re-checking the flag, so a
+        // `trigger()` racing with this call can't be missed between the
+        // check above and the `notified()` registration.
+        let notified = self.notify.notified();
+
+        if self.is_triggered() {
+            return;
+        }
+
+        notified.await;
+    }
+
+    /// Marks one unit of work (e.g. an in-flight `send_message`) as
+    /// outstanding until the returned guard is dropped. [`Shutdown::drain`]
+    /// won't resolve while any guard is still held.
+    pub fn guard(&self) -> ShutdownGuard {
+        self.outstanding.fetch_add(1, Ordering::SeqCst);
+        ShutdownGuard(self.clone())
+    }
+
+    /// Resolves once every [`ShutdownGuard`] handed out by [`Shutdown::guard`]
+    /// has been dropped, immediately if none are outstanding.
+    pub async fn drain(&self) {
+        loop {
+            if self.outstanding.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+
+            let notified = self.drained.notified();
+
+            if self.outstanding.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+
+            notified.await;
+        }
+    }
+}
+
+/// Held for the duration of a unit of work [`Shutdown::drain`] should wait
+/// for. See [`Shutdown::guard`].
+pub struct ShutdownGuard(Shutdown);
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        if self.0.outstanding.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.drained.notify_waiters();
+        }
+    }
+}
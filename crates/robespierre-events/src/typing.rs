@@ -0,0 +1,110 @@
+//! The `BeginTyping`/`EndTyping` half of the gateway protocol, kept alive
+//! for as long as a [`TypingSession`] is held and always sent a final
+//! `EndTyping` — either when it's dropped, or sooner, when the events loop
+//! shuts down.
+
+use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::Duration;
+
+use robespierre_models::{events::ClientToServerEvent, id::ChannelId};
+use tokio::sync::mpsc;
+
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Every [`TypingSession`] still alive, so the events loop can send a final
+/// `EndTyping` for each on shutdown even for sessions the caller hasn't
+/// dropped yet.
+#[derive(Clone, Default)]
+pub(crate) struct Registry(Arc<Mutex<HashMap<u64, ChannelId>>>);
+
+impl Registry {
+    fn insert(&self, id: u64, channel: ChannelId) {
+        self.0.lock().unwrap().insert(id, channel);
+    }
+
+    fn remove(&self, id: u64) -> bool {
+        self.0.lock().unwrap().remove(&id).is_some()
+    }
+
+    /// Takes every still-registered channel, so each only gets sent one
+    /// final `EndTyping` even if its `TypingSession` is dropped after
+    /// shutdown has already drained it.
+    pub(crate) fn drain(&self) -> Vec<ChannelId> {
+        self.0
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, channel)| channel)
+            .collect()
+    }
+}
+
+/// Keeps `channel` marked as typing by resending `BeginTyping` on an
+/// interval for as long as this is held, and sends a final `EndTyping` when
+/// it's dropped. Registered with the events task's session registry so the
+/// loop can send that same `EndTyping` for every still-live session as soon
+/// as [`crate::shutdown::Shutdown::trigger`] fires, rather than whenever the
+/// caller happens to drop its `TypingSession`.
+pub struct TypingSession {
+    id: u64,
+    channel: ChannelId,
+    frames: mpsc::UnboundedSender<ClientToServerEvent>,
+    registry: Registry,
+    resend: tokio::task::JoinHandle<()>,
+}
+
+impl TypingSession {
+    pub(crate) fn start(
+        channel: ChannelId,
+        frames: mpsc::UnboundedSender<ClientToServerEvent>,
+        registry: Registry,
+        resend_interval: Duration,
+    ) -> Self {
+        let id = NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed);
+        registry.insert(id, channel);
+
+        let _ = frames.send(ClientToServerEvent::BeginTyping { channel });
+
+        let resend_frames = frames.clone();
+        let resend = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(resend_interval);
+            ticker.tick().await; // consume the immediate first tick
+
+            loop {
+                ticker.tick().await;
+                if resend_frames
+                    .send(ClientToServerEvent::BeginTyping { channel })
+                    .is_err()
+                {
+                    return;
+                }
+            }
+        });
+
+        Self {
+            id,
+            channel,
+            frames,
+            registry,
+            resend,
+        }
+    }
+}
+
+impl Drop for TypingSession {
+    fn drop(&mut self) {
+        self.resend.abort();
+
+        // If the registry no longer has this session, the events loop
+        // already drained it (and sent its final `EndTyping`) on shutdown.
+        if self.registry.remove(self.id) {
+            let _ = self.frames.send(ClientToServerEvent::EndTyping {
+                channel: self.channel,
+            });
+        }
+    }
+}
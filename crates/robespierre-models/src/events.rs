@@ -30,10 +30,11 @@ pub enum ClientToServerEvent {
     EndTyping {
         channel: ChannelId,
     },
+    /// A heartbeat carrying a monotonically increasing `time` token, matched
+    /// against the [`ServerToClientEvent::Pong`] it provokes to measure
+    /// round-trip latency. See [`Heartbeat`].
     Ping {
         time: u32,
-        #[deprecated(note = "Temporary fix for temporary issue")]
-        data: (u8,),
     },
 }
 
@@ -46,6 +47,241 @@ pub struct ReadyEvent {
     pub members: Vec<Member>,
 }
 
+impl ReadyEvent {
+    /// Diffs this `ReadyEvent` against the one received before a gateway
+    /// reconnect and synthesizes the [`ServerToClientEvent`]s needed to
+    /// reconcile a cache that was built from `previous` into one consistent
+    /// with `self`, without a full cache rebuild.
+    ///
+    /// Revolt has no resume token, so a reconnect always hands back a full
+    /// `Ready` payload; this turns that payload into the same stream of
+    /// deltas a client would have seen had it stayed connected. Entities
+    /// that disappeared between the two snapshots become `*Delete` (or
+    /// `ServerMemberLeave`) events, and entities that are new become
+    /// `*Create` (or `ServerMemberJoin`) events.
+    ///
+    /// Entities present in both snapshots but changed get a field-level
+    /// `*Update` event, built from a full-snapshot [`PartialServer`] /
+    /// [`PartialChannel`] / [`PartialMember`] / [`PartialUser`] (every field
+    /// populated, `clear: None`) rather than a true per-field diff: this
+    /// crate has no access to which individual fields moved, only whether
+    /// the entity as a whole did. A consumer that needs finer-grained
+    /// `clear` handling can still diff the `Partial*` itself against what it
+    /// had cached.
+    ///
+    /// Users only get `UserUpdate` events, for users present in both
+    /// snapshots that changed. The gateway has no
+    /// `UserDelete`/`UserRelationship`-style "gone" signal for a user that
+    /// simply dropped out of the `Ready` payload (unlike servers, channels
+    /// and members, which do), so a user present in `previous` but absent
+    /// from `current` produces no event here; nor is there a user-create
+    /// event, so a user present in `current` but not `previous` produces no
+    /// event either.
+    ///
+    /// Servers have a matching gap in the other direction: there is no
+    /// `ServerCreate` in [`ServerToClientEvent`] (a client only ever learns
+    /// about a server through the initial `Ready` or a `ServerMemberJoin`
+    /// naming itself), so a server present in `current` but not `previous`
+    /// produces no event here either — and, to avoid handing a consumer a
+    /// `ChannelCreate`/`ServerMemberJoin` for a server it was never told
+    /// exists, the channel and member loops below skip entities belonging to
+    /// such a server. A bot added to a new server while disconnected still
+    /// needs a full cache rebuild to pick it up; `reconcile` alone can't
+    /// cover that case.
+    pub fn reconcile(previous: &ReadyEvent, current: &ReadyEvent) -> Vec<ServerToClientEvent> {
+        let mut events = Vec::new();
+
+        let prev_servers: std::collections::HashMap<_, _> =
+            previous.servers.iter().map(|s| (s.id, s)).collect();
+        let curr_server_ids: std::collections::HashSet<_> =
+            current.servers.iter().map(|s| s.id).collect();
+        let new_server_ids: std::collections::HashSet<_> = current
+            .servers
+            .iter()
+            .map(|s| s.id)
+            .filter(|id| !prev_servers.contains_key(id))
+            .collect();
+
+        for server in &previous.servers {
+            if !curr_server_ids.contains(&server.id) {
+                events.push(ServerToClientEvent::ServerDelete { id: server.id });
+            }
+        }
+        for server in &current.servers {
+            if let Some(&previous) = prev_servers.get(&server.id) {
+                if previous != server {
+                    events.push(ServerToClientEvent::ServerUpdate {
+                        id: server.id,
+                        data: server.clone().into(),
+                        clear: None,
+                    });
+                }
+            }
+        }
+
+        let prev_channels: std::collections::HashMap<_, _> =
+            previous.channels.iter().map(|c| (c.id(), c)).collect();
+        let curr_channel_ids: std::collections::HashSet<_> =
+            current.channels.iter().map(|c| c.id()).collect();
+
+        for channel in &previous.channels {
+            if !curr_channel_ids.contains(&channel.id()) {
+                events.push(ServerToClientEvent::ChannelDelete { id: channel.id() });
+            }
+        }
+        for channel in &current.channels {
+            // A channel belonging to a server `reconcile` never announced
+            // (see the doc comment above) would otherwise be an orphaned
+            // `ChannelCreate` pointing at a server the consumer doesn't know
+            // about yet.
+            if channel
+                .server_id()
+                .is_some_and(|id| new_server_ids.contains(&id))
+            {
+                continue;
+            }
+
+            match prev_channels.get(&channel.id()) {
+                None => events.push(ServerToClientEvent::ChannelCreate {
+                    channel: channel.clone(),
+                }),
+                Some(&previous) if previous != channel => {
+                    events.push(ServerToClientEvent::ChannelUpdate {
+                        id: channel.id(),
+                        data: channel.clone().into(),
+                        clear: None,
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+
+        let prev_members: std::collections::HashMap<_, _> =
+            previous.members.iter().map(|m| (m.id, m)).collect();
+        let curr_member_ids: std::collections::HashSet<_> =
+            current.members.iter().map(|m| m.id).collect();
+
+        for member in &previous.members {
+            if !curr_member_ids.contains(&member.id) {
+                events.push(ServerToClientEvent::ServerMemberLeave {
+                    id: member.id.server,
+                    user: member.id.user,
+                });
+            }
+        }
+        for member in &current.members {
+            // Same orphan guard as the channel loop above: skip members of a
+            // server `reconcile` never announced.
+            if new_server_ids.contains(&member.id.server) {
+                continue;
+            }
+
+            match prev_members.get(&member.id) {
+                None => events.push(ServerToClientEvent::ServerMemberJoin {
+                    id: member.id.server,
+                    user: member.id.user,
+                }),
+                Some(&previous) if previous != member => {
+                    events.push(ServerToClientEvent::ServerMemberUpdate {
+                        id: member.id,
+                        data: member.clone().into(),
+                        clear: None,
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+
+        let prev_users: std::collections::HashMap<_, _> =
+            previous.users.iter().map(|u| (u.id, u)).collect();
+        for user in &current.users {
+            if let Some(&previous) = prev_users.get(&user.id) {
+                if previous != user {
+                    events.push(ServerToClientEvent::UserUpdate {
+                        id: user.id,
+                        data: user.clone().into(),
+                        clear: None,
+                    });
+                }
+            }
+        }
+
+        events
+    }
+}
+
+/// Where the gateway connection currently is in its lifecycle, as observed
+/// through the automatic reconnection layer.
+///
+/// This is the stream surfaced to applications that want to react to a
+/// reconnect (e.g. to warn that in-flight state may have been resynced); the
+/// reconnect/backoff loop that drives these transitions lives in the events
+/// task, not in this crate.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ConnectionState {
+    /// The initial connection attempt is in progress.
+    Connecting,
+    /// Authenticated and a `Ready` event has been received.
+    Connected,
+    /// The websocket dropped and a reconnect attempt is in progress.
+    Reconnecting,
+    /// Reconnected and reconciling the cache against a fresh `Ready` event.
+    Resyncing,
+}
+
+/// Tracks an in-flight heartbeat `Ping`/`Pong` round trip.
+///
+/// The events task owns the socket and the interval timer that decides
+/// *when* to send a [`ClientToServerEvent::Ping`]; this only knows how to
+/// mint the next one, match the [`ServerToClientEvent::Pong`] it provokes,
+/// and report the resulting latency (or that none arrived in time).
+#[derive(Debug)]
+pub struct Heartbeat {
+    next_time: u32,
+    pending: Option<(u32, std::time::Instant)>,
+}
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Self {
+            next_time: 0,
+            pending: None,
+        }
+    }
+
+    /// Mints the next `Ping` to send and starts timing its round trip.
+    pub fn ping(&mut self) -> ClientToServerEvent {
+        let time = self.next_time;
+        self.next_time = self.next_time.wrapping_add(1);
+        self.pending = Some((time, std::time::Instant::now()));
+        ClientToServerEvent::Ping { time }
+    }
+
+    /// Matches a received `Pong` against the in-flight ping, returning the
+    /// measured round-trip latency if `time` matches what was last sent.
+    pub fn pong(&mut self, time: u32) -> Option<std::time::Duration> {
+        match self.pending.take() {
+            Some((expected, sent_at)) if expected == time => Some(sent_at.elapsed()),
+            other => {
+                self.pending = other;
+                None
+            }
+        }
+    }
+
+    /// Whether the in-flight ping (if any) has gone unanswered for longer
+    /// than `timeout`, meaning the connection should be treated as dead.
+    pub fn is_dead(&self, timeout: std::time::Duration) -> bool {
+        matches!(self.pending, Some((_, sent_at)) if sent_at.elapsed() > timeout)
+    }
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Any message that the server can send to the client.
 #[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
 #[serde(tag = "type")]
@@ -154,3 +390,56 @@ pub enum ServerToClientEvent {
         status: RelationshipStatus,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `ReadyEvent::reconcile` isn't covered here: exercising it needs fixture
+    // `User`/`Server`/`Channel`/`Member` values (and their `*Id` types),
+    // whose field layouts are owned by `crate::user`, `crate::server`,
+    // `crate::channel`, and `crate::id`, not by this file. These tests only
+    // cover `Heartbeat`.
+
+    #[test]
+    fn pong_matches_the_pending_ping_and_reports_latency() {
+        let mut heartbeat = Heartbeat::new();
+        let ping = heartbeat.ping();
+        let time = match ping {
+            ClientToServerEvent::Ping { time } => time,
+            _ => unreachable!(),
+        };
+
+        assert!(heartbeat.pong(time).is_some());
+    }
+
+    #[test]
+    fn pong_with_a_stale_time_is_ignored_and_ping_stays_pending() {
+        let mut heartbeat = Heartbeat::new();
+        let ping = heartbeat.ping();
+        let time = match ping {
+            ClientToServerEvent::Ping { time } => time,
+            _ => unreachable!(),
+        };
+
+        assert!(heartbeat.pong(time.wrapping_add(1)).is_none());
+        // The real ping is still pending, so it can still be matched later.
+        assert!(heartbeat.pong(time).is_some());
+    }
+
+    #[test]
+    fn pong_with_no_pending_ping_is_ignored() {
+        let mut heartbeat = Heartbeat::new();
+        assert!(heartbeat.pong(0).is_none());
+    }
+
+    #[test]
+    fn is_dead_only_once_the_pending_ping_outlives_the_timeout() {
+        let mut heartbeat = Heartbeat::new();
+        assert!(!heartbeat.is_dead(std::time::Duration::from_secs(30)));
+
+        heartbeat.ping();
+        assert!(!heartbeat.is_dead(std::time::Duration::from_secs(30)));
+        assert!(heartbeat.is_dead(std::time::Duration::from_secs(0)));
+    }
+}
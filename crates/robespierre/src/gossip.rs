@@ -0,0 +1,341 @@
+//! Distributed cache mode for multi-process sharded bots.
+//!
+//! Each shard disseminates the deltas it applies through
+//! `robespierre_cache::CommitToCache` to the rest of the mesh over UDP, so a
+//! peer that needs an entity another shard already fetched over HTTP gets a
+//! cross-process cache hit instead of a redundant request. Since UDP drops
+//! and reorders datagrams, peers also run an anti-entropy round on a timer
+//! ([`GossipNode::run_anti_entropy`]): each side exchanges its version
+//! vector and pulls whatever the digest says it's missing, so a dropped
+//! datagram gets repaired instead of leaving that peer's cache stale forever.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::{net::UdpSocket, sync::RwLock};
+
+const MAX_DATAGRAM_SIZE: usize = 64 * 1024;
+
+/// A cache delta disseminated between shards: the entity that changed,
+/// tagged with a logical `version` so peers can tell a fresher update from
+/// a stale, out-of-order one arriving late over UDP.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GossipMessage<T> {
+    pub entity_id: String,
+    pub version: u64,
+    pub entity: T,
+}
+
+/// What actually goes over the wire: either a cache delta (with the entity
+/// kept as an opaque, already-encoded payload, so this frame doesn't need
+/// to know the entity's concrete type) or one half of an anti-entropy
+/// exchange.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Frame {
+    Push {
+        entity_id: String,
+        version: u64,
+        payload: Vec<u8>,
+    },
+    /// "Here's the highest version I have for each entity" — sent to every
+    /// peer on the anti-entropy timer.
+    Digest(HashMap<String, u64>),
+    /// "I'm behind on these IDs; `Push` them to me" — a reply to a `Digest`
+    /// that named entries this node's version vector is missing or stale
+    /// on.
+    Pull(Vec<String>),
+}
+
+/// Tracks the highest version seen per entity ID, so gossip that raced an
+/// already-applied, newer update is dropped rather than clobbering the
+/// cache backwards (last-writer-wins, keyed by entity ID). Also keeps the
+/// encoded payload of the last-applied version around, so a `Pull` from a
+/// peer that fell behind can be answered without re-fetching the entity.
+#[derive(Debug, Default)]
+struct VersionVector(HashMap<String, (u64, Vec<u8>)>);
+
+impl VersionVector {
+    /// Records `version`/`payload` for `entity_id` if `version` is newer
+    /// than what's known, returning whether the accompanying update should
+    /// be applied.
+    ///
+    /// Unlike [`VersionVector::missing_from`]'s `peer_digest` (a plain
+    /// `HashMap<String, u64>` with no way to distinguish "never seen" from
+    /// "seen at version 0"), `self.0` doesn't have that ambiguity: matching
+    /// on `self.0.get(entity_id)` directly tells apart "never observed"
+    /// (`None`, always applied) from "observed at some version" (`Some`,
+    /// applied only if newer) — `0` is a perfectly ordinary first version
+    /// here, not a stand-in for "absent".
+    fn observe(&mut self, entity_id: &str, version: u64, payload: &[u8]) -> bool {
+        match self.0.get(entity_id) {
+            Some((known, _)) if *known >= version => false,
+            _ => {
+                self.0
+                    .insert(entity_id.to_owned(), (version, payload.to_owned()));
+                true
+            }
+        }
+    }
+
+    fn snapshot(&self) -> HashMap<String, u64> {
+        self.0
+            .iter()
+            .map(|(id, (version, _))| (id.clone(), *version))
+            .collect()
+    }
+
+    fn payload(&self, entity_id: &str) -> Option<(u64, Vec<u8>)> {
+        self.0.get(entity_id).cloned()
+    }
+
+    /// Entity IDs this node has a newer version of than `peer_digest`
+    /// claims to know about — including any entity `peer_digest` has no
+    /// entry for at all, which must be treated as "peer has never seen
+    /// this", not as "peer's version is 0": a real, valid first version is
+    /// `0` (see [`VersionVector::observe`]), so collapsing "absent" into
+    /// that via `unwrap_or(0)` would make a dropped initial `Push` for a
+    /// `0`-versioned entity unrepairable by anti-entropy forever.
+    fn missing_from(&self, peer_digest: &HashMap<String, u64>) -> Vec<String> {
+        self.0
+            .iter()
+            .filter(|(id, (version, _))| match peer_digest.get(*id) {
+                None => true,
+                Some(peer_version) => peer_version < version,
+            })
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
+
+/// A peer participating in the gossip mesh: a UDP socket plus the set of
+/// other nodes deltas get disseminated to.
+pub struct GossipNode {
+    socket: UdpSocket,
+    peers: RwLock<Vec<SocketAddr>>,
+    versions: RwLock<VersionVector>,
+}
+
+impl GossipNode {
+    pub async fn bind(addr: SocketAddr, peers: Vec<SocketAddr>) -> std::io::Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind(addr).await?,
+            peers: RwLock::new(peers),
+            versions: RwLock::new(VersionVector::default()),
+        })
+    }
+
+    pub async fn add_peer(&self, peer: SocketAddr) {
+        self.peers.write().await.push(peer);
+    }
+
+    /// Disseminates a locally-applied cache delta (an entity create or a
+    /// `Partial*` update produced by `commit_to_cache`) to every known peer,
+    /// and records it in this node's own version vector so a later
+    /// anti-entropy round can repair a peer that missed the datagram.
+    pub async fn publish<T: Serialize>(
+        &self,
+        entity_id: impl Into<String>,
+        version: u64,
+        entity: &T,
+    ) -> std::io::Result<()> {
+        let entity_id = entity_id.into();
+        let payload = bincode::serialize(entity).expect("entity is always serializable");
+
+        self.versions
+            .write()
+            .await
+            .observe(&entity_id, version, &payload);
+
+        self.send_frame(
+            &Frame::Push {
+                entity_id,
+                version,
+                payload,
+            },
+            None,
+        )
+        .await
+    }
+
+    /// Receives the next gossip datagram. A [`Frame::Push`] that isn't
+    /// stale per the version vector is decoded as `T` and handed to
+    /// `commit` to be applied through the local `robespierre_cache` via the
+    /// same `CommitToCache` path HTTP-sourced entities take; a
+    /// [`Frame::Digest`] or [`Frame::Pull`] (the anti-entropy exchange) is
+    /// answered directly and never reaches `commit`.
+    pub async fn recv_and_apply<T, F, Fut>(&self, commit: F) -> std::io::Result<()>
+    where
+        T: DeserializeOwned,
+        F: FnOnce(T) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+        let (len, peer) = self.socket.recv_from(&mut buf).await?;
+
+        let frame: Frame = match bincode::deserialize(&buf[..len]) {
+            Ok(frame) => frame,
+            // Not one of ours; gossip is best-effort, so drop and move on.
+            Err(_) => return Ok(()),
+        };
+
+        match frame {
+            Frame::Push {
+                entity_id,
+                version,
+                payload,
+            } => {
+                let entity: T = match bincode::deserialize(&payload) {
+                    Ok(entity) => entity,
+                    Err(_) => return Ok(()),
+                };
+
+                let should_apply = self
+                    .versions
+                    .write()
+                    .await
+                    .observe(&entity_id, version, &payload);
+
+                if should_apply {
+                    commit(entity).await;
+                }
+            }
+            Frame::Digest(peer_digest) => {
+                // Pull whatever the sender has a newer version of than this
+                // node (including entities this node has never seen).
+                let versions = self.versions.read().await;
+                let missing: Vec<String> = peer_digest
+                    .iter()
+                    .filter(|(id, peer_version)| match versions.0.get(*id) {
+                        None => true,
+                        Some((known, _)) => known < *peer_version,
+                    })
+                    .map(|(id, _)| id.clone())
+                    .collect();
+                drop(versions);
+                if !missing.is_empty() {
+                    self.send_frame(&Frame::Pull(missing), Some(peer)).await?;
+                }
+            }
+            Frame::Pull(ids) => {
+                let versions = self.versions.read().await;
+                for id in ids {
+                    if let Some((version, payload)) = versions.payload(&id) {
+                        self.send_frame(
+                            &Frame::Push {
+                                entity_id: id,
+                                version,
+                                payload,
+                            },
+                            Some(peer),
+                        )
+                        .await?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the anti-entropy loop forever: every `interval`, sends this
+    /// node's version vector to every peer as a [`Frame::Digest`]. Each
+    /// peer answers with a [`Frame::Pull`] for whatever it's missing, which
+    /// `recv_and_apply` on this node answers with the matching `Push`es —
+    /// so a datagram dropped by UDP gets repaired within one `interval`
+    /// instead of waiting for the next unrelated `publish`.
+    pub async fn run_anti_entropy(&self, interval: Duration) -> std::io::Result<()> {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let digest = self.anti_entropy_digest().await;
+            self.send_frame(&Frame::Digest(digest), None).await?;
+        }
+    }
+
+    /// This node's version vector, to be exchanged with a peer during an
+    /// anti-entropy round so a node that missed a datagram can discover
+    /// what it's missing and pull it, rather than waiting for the next
+    /// push to happen to reach it.
+    pub async fn anti_entropy_digest(&self) -> HashMap<String, u64> {
+        self.versions.read().await.snapshot()
+    }
+
+    /// Given a peer's digest, returns the entity IDs this node has a newer
+    /// version of than the peer claims to know about — the set worth
+    /// re-publishing to help that peer converge.
+    pub async fn entries_missing_from(&self, peer_digest: &HashMap<String, u64>) -> Vec<String> {
+        self.versions.read().await.missing_from(peer_digest)
+    }
+
+    /// Sends `frame` to `only`, or to every known peer if `only` is `None`.
+    async fn send_frame(&self, frame: &Frame, only: Option<SocketAddr>) -> std::io::Result<()> {
+        let bytes = bincode::serialize(frame).expect("Frame is always serializable");
+
+        match only {
+            Some(peer) => {
+                self.socket.send_to(&bytes, peer).await?;
+            }
+            None => {
+                for peer in self.peers.read().await.iter() {
+                    self.socket.send_to(&bytes, peer).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn observe_applies_the_first_version_seen_for_an_entity_even_if_zero() {
+        let mut versions = VersionVector::default();
+        assert!(versions.observe("a", 0, b"payload"));
+        assert_eq!(versions.snapshot().get("a"), Some(&0));
+    }
+
+    #[test]
+    fn observe_rejects_a_stale_or_equal_version() {
+        let mut versions = VersionVector::default();
+        versions.observe("a", 5, b"v5");
+        assert!(!versions.observe("a", 5, b"v5-again"));
+        assert!(!versions.observe("a", 3, b"stale"));
+        assert!(versions.observe("a", 6, b"v6"));
+    }
+
+    #[test]
+    fn missing_from_flags_an_entity_absent_from_the_peer_digest_even_at_version_zero() {
+        let mut versions = VersionVector::default();
+        versions.observe("a", 0, b"payload");
+
+        // An empty digest means the peer has never seen "a" at all — not
+        // "peer has version 0" — so it must show up as missing.
+        assert_eq!(versions.missing_from(&HashMap::new()), vec!["a".to_owned()]);
+    }
+
+    #[test]
+    fn missing_from_is_quiet_when_the_peer_already_has_the_same_version() {
+        let mut versions = VersionVector::default();
+        versions.observe("a", 0, b"payload");
+
+        let mut peer_digest = HashMap::new();
+        peer_digest.insert("a".to_owned(), 0);
+        assert!(versions.missing_from(&peer_digest).is_empty());
+    }
+
+    #[test]
+    fn missing_from_flags_an_entity_the_peer_has_an_older_version_of() {
+        let mut versions = VersionVector::default();
+        versions.observe("a", 5, b"payload");
+
+        let mut peer_digest = HashMap::new();
+        peer_digest.insert("a".to_owned(), 2);
+        assert_eq!(versions.missing_from(&peer_digest), vec!["a".to_owned()]);
+    }
+}
@@ -0,0 +1,171 @@
+//! Ergonomic, cache-aware bindings on top of the Revolt gateway/REST API.
+//!
+//! [`Context`] is the handle every extension trait in [`model`] takes: it
+//! carries the HTTP client, the optional cache, and, with the `events`
+//! feature, the live connection state and heartbeat latency kept up to date
+//! by the `robespierre_events` reconnect loop.
+
+pub mod model;
+
+use std::fmt;
+
+#[cfg(feature = "cache")]
+use std::sync::Arc;
+
+#[cfg(feature = "events")]
+use robespierre_models::{events::ConnectionState, id::ChannelId};
+
+/// Errors surfaced by [`model`]'s extension traits. The only variant is a
+/// failed REST call.
+#[derive(Debug)]
+pub enum Error {
+    Http(robespierre_http::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Http(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<robespierre_http::Error> for Error {
+    fn from(err: robespierre_http::Error) -> Self {
+        Error::Http(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Implemented by anything that can hand out the HTTP client, with no
+/// guarantee of a cache sitting in front of it. `send_message` and other
+/// write operations only ever need this, since there's nothing to cache a
+/// write against.
+pub trait HasHttp: Send + Sync {
+    fn get_http(&self) -> &robespierre_http::Http;
+
+    /// Holds open one unit of outstanding work against this handle's
+    /// [`model::shutdown::Shutdown`] token for as long as the returned guard
+    /// is held, so a shutdown triggered mid-request waits for
+    /// `ChannelIdExt::send_message` to finish instead of the events loop
+    /// closing the socket out from under it. `None` by default — only [`Context`] (with the
+    /// `events` feature, and a [`robespierre_events::Handle`] attached) has
+    /// a shutdown token to guard.
+    #[cfg(feature = "events")]
+    fn shutdown_guard(&self) -> Option<robespierre_events::shutdown::ShutdownGuard> {
+        None
+    }
+}
+
+/// Implemented by anything that can hand out the HTTP client *and*, when the
+/// `cache` feature is enabled, an optional cache to check before falling
+/// back to it.
+pub trait CacheHttp: HasHttp {
+    fn http(&self) -> &robespierre_http::Http {
+        self.get_http()
+    }
+
+    #[cfg(feature = "cache")]
+    fn cache(&self) -> Option<&robespierre_cache::Cache>;
+}
+
+/// The handle passed to every [`model`] extension trait: the HTTP client,
+/// the optional cache, and, with the `events` feature, the
+/// [`robespierre_events::Handle`] onto the reconnect loop driving this bot's
+/// gateway connection — connection state, heartbeat latency, the shutdown
+/// token, and the means to start a [`model::shutdown::Shutdown`]-aware
+/// typing session.
+pub struct Context {
+    http: robespierre_http::Http,
+    #[cfg(feature = "cache")]
+    cache: Option<Arc<robespierre_cache::Cache>>,
+    #[cfg(feature = "events")]
+    events: Option<robespierre_events::Handle>,
+}
+
+impl Context {
+    pub fn new(http: robespierre_http::Http) -> Self {
+        Self {
+            http,
+            #[cfg(feature = "cache")]
+            cache: None,
+            #[cfg(feature = "events")]
+            events: None,
+        }
+    }
+
+    #[cfg(feature = "cache")]
+    pub fn with_cache(mut self, cache: Arc<robespierre_cache::Cache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Attaches the [`robespierre_events::Handle`] for a `robespierre_events`
+    /// task driving this bot's connection, so [`Context::connection_state`],
+    /// [`Context::latency`], [`Context::start_typing`], and
+    /// `ChannelIdExt::send_message`'s shutdown-draining all report and react
+    /// to that task's view. On a bare `Context::new`, `connection_state` and
+    /// `latency` return `None`, `send_message` holds no shutdown guard, and
+    /// `start_typing` panics.
+    #[cfg(feature = "events")]
+    pub fn with_events(mut self, events: robespierre_events::Handle) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Where the gateway connection currently is, as last published by the
+    /// reconnect loop. `None` if no [`robespierre_events::Handle`] was
+    /// attached via [`Context::with_events`].
+    #[cfg(feature = "events")]
+    pub fn connection_state(&self) -> Option<ConnectionState> {
+        self.events.as_ref().map(|events| events.connection_state())
+    }
+
+    /// The most recently measured heartbeat round-trip latency, or `None`
+    /// if no `Pong` has been matched yet (including while disconnected, or
+    /// with no [`robespierre_events::Handle`] attached).
+    #[cfg(feature = "events")]
+    pub fn latency(&self) -> Option<std::time::Duration> {
+        self.events.as_ref().and_then(|events| events.latency())
+    }
+
+    /// Starts a typing indicator in `channel`, kept alive for as long as the
+    /// returned [`robespierre_events::typing::TypingSession`] is held.
+    /// Panics if no [`robespierre_events::Handle`] was attached via
+    /// [`Context::with_events`] — a bot using `start_typing` always runs the
+    /// events loop.
+    #[cfg(feature = "events")]
+    pub fn start_typing(&self, channel: ChannelId) -> robespierre_events::typing::TypingSession {
+        self.events
+            .as_ref()
+            .expect("start_typing called on a Context with no events::Handle attached")
+            .start_typing(channel)
+    }
+}
+
+impl HasHttp for Context {
+    fn get_http(&self) -> &robespierre_http::Http {
+        &self.http
+    }
+
+    #[cfg(feature = "events")]
+    fn shutdown_guard(&self) -> Option<robespierre_events::shutdown::ShutdownGuard> {
+        self.events.as_ref().map(|events| events.shutdown().guard())
+    }
+}
+
+impl CacheHttp for Context {
+    #[cfg(feature = "cache")]
+    fn cache(&self) -> Option<&robespierre_cache::Cache> {
+        self.cache.as_deref()
+    }
+}
+
+impl AsRef<Context> for Context {
+    fn as_ref(&self) -> &Context {
+        self
+    }
+}
@@ -0,0 +1,160 @@
+//! Formatting and parsing for Revolt's mention syntax.
+
+use robespierre_models::id::{ChannelId, RoleId, UserId};
+
+/// Formats a user mention the way Revolt's markdown dialect expects it:
+/// `<@id>`. The client renders this into a pill showing the user's current
+/// name, so it carries no display text of its own.
+pub fn user(id: UserId) -> String {
+    format!("<@{}>", id)
+}
+
+/// Formats a role mention: `<%id>`.
+pub fn role(id: RoleId) -> String {
+    format!("<%{}>", id)
+}
+
+/// Formats a channel mention: `<#id>`.
+pub fn channel(id: ChannelId) -> String {
+    format!("<#{}>", id)
+}
+
+/// Conversion between Revolt's `<@id>` mention syntax and the plain-text
+/// representation a non-Revolt bridge target (IRC, XMPP, Matrix, ...)
+/// expects, so a `projection::Projection` implementor gets message-body
+/// translation for free.
+pub mod plain {
+    /// Rewrites every `<@id>` mention in `content` into `@name`, looking up
+    /// each `id` with `resolve`. An `id` that `resolve` can't place is left
+    /// as the raw mention rather than dropped, so the bridge doesn't erase
+    /// information it couldn't translate.
+    pub fn to_plain(content: &str, resolve: impl Fn(&str) -> Option<String>) -> String {
+        let mut out = String::with_capacity(content.len());
+        let mut rest = content;
+
+        while let Some(start) = rest.find("<@") {
+            out.push_str(&rest[..start]);
+            let after_marker = &rest[start + 2..];
+
+            match after_marker.find('>') {
+                Some(end) => {
+                    let id = &after_marker[..end];
+                    match resolve(id) {
+                        Some(name) => {
+                            out.push('@');
+                            out.push_str(&name);
+                        }
+                        None => out.push_str(&rest[start..start + 2 + end + 1]),
+                    }
+                    rest = &after_marker[end + 1..];
+                }
+                None => {
+                    out.push_str(&rest[start..]);
+                    rest = "";
+                    break;
+                }
+            }
+        }
+
+        out.push_str(rest);
+        out
+    }
+
+    /// Rewrites every `@name` reference in `content` into a Revolt `<@id>`
+    /// mention, looking up each `name` with `resolve`. A `name` runs up to
+    /// the first character that isn't alphanumeric or `_`, so trailing
+    /// punctuation (`@alice,`) isn't part of it. A `name` that `resolve`
+    /// can't place is left as plain text.
+    pub fn from_plain(content: &str, resolve: impl Fn(&str) -> Option<String>) -> String {
+        let mut out = String::with_capacity(content.len());
+        let mut rest = content;
+
+        while let Some(start) = rest.find('@') {
+            out.push_str(&rest[..start]);
+            let after_marker = &rest[start + 1..];
+            let end = after_marker
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(after_marker.len());
+            let name = &after_marker[..end];
+
+            if name.is_empty() {
+                out.push('@');
+                rest = after_marker;
+                continue;
+            }
+
+            match resolve(name) {
+                Some(id) => out.push_str(&super::user_raw(&id)),
+                None => out.push_str(&rest[start..start + 1 + end]),
+            }
+            rest = &after_marker[end..];
+        }
+
+        out.push_str(rest);
+        out
+    }
+}
+
+/// Formats a user mention from a raw, already-resolved ID string. Used by
+/// [`plain::from_plain`], which only has a name → ID string mapping to work
+/// from rather than a parsed [`UserId`].
+fn user_raw(id: &str) -> String {
+    format!("<@{}>", id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::plain::{from_plain, to_plain};
+
+    #[test]
+    fn to_plain_resolves_known_mentions() {
+        let out = to_plain("hey <@abc>, see <@def>", |id| match id {
+            "abc" => Some("alice".to_owned()),
+            "def" => Some("dave".to_owned()),
+            _ => None,
+        });
+        assert_eq!(out, "hey @alice, see @dave");
+    }
+
+    #[test]
+    fn to_plain_leaves_unresolvable_mentions_raw() {
+        let out = to_plain("hey <@unknown>", |_| None);
+        assert_eq!(out, "hey <@unknown>");
+    }
+
+    #[test]
+    fn to_plain_handles_an_unterminated_mention() {
+        let out = to_plain("hey <@abc", |_| Some("alice".to_owned()));
+        assert_eq!(out, "hey <@abc");
+    }
+
+    #[test]
+    fn from_plain_resolves_known_names() {
+        let out = from_plain("hey @alice, see @dave", |name| match name {
+            "alice" => Some("abc".to_owned()),
+            "dave" => Some("def".to_owned()),
+            _ => None,
+        });
+        assert_eq!(out, "hey <@abc>, see <@def>");
+    }
+
+    #[test]
+    fn from_plain_leaves_unresolvable_names_raw() {
+        let out = from_plain("hey @unknown", |_| None);
+        assert_eq!(out, "hey @unknown");
+    }
+
+    #[test]
+    fn from_plain_leaves_a_lone_at_sign_untouched() {
+        let out = from_plain("look @ here", |_| Some("x".to_owned()));
+        assert_eq!(out, "look @ here");
+    }
+
+    #[test]
+    fn to_plain_then_from_plain_round_trips() {
+        let original = "hey <@abc>, welcome!";
+        let plain = to_plain(original, |id| (id == "abc").then(|| "alice".to_owned()));
+        let back = from_plain(&plain, |name| (name == "alice").then(|| "abc".to_owned()));
+        assert_eq!(back, original);
+    }
+}
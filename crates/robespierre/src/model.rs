@@ -4,14 +4,45 @@ use robespierre_cache::CommitToCache;
 use robespierre_events::typing::TypingSession;
 use robespierre_models::{
     channel::{Channel, Message, ReplyData},
-    id::{AttachmentId, ChannelId, ServerId, UserId},
+    id::{AttachmentId, ChannelId, MessageId, ServerId, UserId},
     server::Server,
     user::User,
 };
 
 use crate::{CacheHttp, Context, HasHttp, Result};
 
+#[cfg(feature = "gossip")]
+pub mod gossip;
 pub mod mention;
+pub mod projection;
+#[cfg(feature = "events")]
+pub mod shutdown;
+
+/// Records `robespierre_http_request_duration_seconds` and
+/// `robespierre_http_requests_total` for a call to `endpoint`, tagging the
+/// status counter with whether `result` succeeded.
+///
+/// This only records into whatever `metrics::Recorder` the host application
+/// has installed — this crate doesn't install one itself, set up a registry
+/// to scrape, or wire an OTLP exporter. That's the host's job (e.g.
+/// `metrics_exporter_prometheus::PrometheusBuilder` or an OTLP metrics
+/// pipeline), the same way `tracing`'s `#[instrument]` spans here need a
+/// `tracing_subscriber` the host installs to go anywhere.
+#[cfg(feature = "metrics")]
+fn record_http_metrics<T, E>(
+    endpoint: &'static str,
+    started_at: std::time::Instant,
+    result: &std::result::Result<T, E>,
+) {
+    metrics::histogram!("robespierre_http_request_duration_seconds", "endpoint" => endpoint)
+        .record(started_at.elapsed().as_secs_f64());
+    metrics::counter!(
+        "robespierre_http_requests_total",
+        "endpoint" => endpoint,
+        "status" => if result.is_ok() { "ok" } else { "error" },
+    )
+    .increment(1);
+}
 
 pub trait IntoString: Into<String> + Send + Sync + 'static {}
 impl<T> IntoString for T where T: Into<String> + Send + Sync + 'static {}
@@ -23,7 +54,10 @@ impl<T> AsRefContext for T where T: AsRef<Context> + Send + Sync + 'static {}
 #[cfg(not(feature = "cache"))]
 #[async_trait::async_trait]
 trait CommitToCache {
-    async fn commit_to_cache<T>(self, cache: T) -> Self where Self: Sized {
+    async fn commit_to_cache<T>(self, cache: T) -> Self
+    where
+        Self: Sized,
+    {
         self
     }
 }
@@ -119,6 +153,84 @@ impl MessageExt for Message {
     }
 }
 
+/// The maximum number of messages the server will return from a single
+/// `fetch_messages` call, regardless of the `limit` requested.
+const MAX_FETCH_MESSAGES_LIMIT: usize = 100;
+
+/// Selects how a page of message history should be fetched, mirroring the
+/// query modes of Revolt's `fetch_messages` endpoint (and, by extension,
+/// IRC's CHATHISTORY capability).
+///
+/// Whatever variant is used, [`ChannelIdExt::fetch_messages`] always returns
+/// its `Vec<Message>` sorted oldest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageQuery {
+    /// The most recent `limit` messages in the channel.
+    Latest { limit: usize },
+    /// Up to `limit` messages sent before `id`, not including `id` itself.
+    Before { id: MessageId, limit: usize },
+    /// Up to `limit` messages sent after `id`, not including `id` itself.
+    After { id: MessageId, limit: usize },
+    /// Up to `limit` messages surrounding `id`, with `id` itself included
+    /// and placed in the middle of the returned window.
+    Around { id: MessageId, limit: usize },
+    /// Up to `limit` messages sent between `start` and `end`, not including
+    /// `start` or `end` themselves (the same `before`/`after` semantics as
+    /// [`MessageQuery::Before`]/[`MessageQuery::After`]).
+    Between {
+        start: MessageId,
+        end: MessageId,
+        limit: usize,
+    },
+}
+
+impl MessageQuery {
+    fn limit(&self) -> usize {
+        match *self {
+            MessageQuery::Latest { limit }
+            | MessageQuery::Before { limit, .. }
+            | MessageQuery::After { limit, .. }
+            | MessageQuery::Around { limit, .. }
+            | MessageQuery::Between { limit, .. } => limit,
+        }
+        .min(MAX_FETCH_MESSAGES_LIMIT)
+    }
+
+    /// Maps this query onto Revolt's `fetch_messages` query params:
+    /// `(before, after, sort, nearby, limit)`.
+    fn to_http_params(
+        self,
+    ) -> (
+        Option<MessageId>,
+        Option<MessageId>,
+        &'static str,
+        Option<MessageId>,
+        usize,
+    ) {
+        let limit = self.limit();
+
+        let (before, after, sort, nearby) = match self {
+            MessageQuery::Latest { .. } => (None, None, "Latest", None),
+            MessageQuery::Before { id, .. } => (Some(id), None, "Latest", None),
+            MessageQuery::After { id, .. } => (None, Some(id), "Oldest", None),
+            MessageQuery::Around { id, .. } => (None, None, "Latest", Some(id)),
+            MessageQuery::Between { start, end, .. } => (Some(end), Some(start), "Oldest", None),
+        };
+
+        (before, after, sort, nearby, limit)
+    }
+
+    /// Whether the server returns this query's results newest-first,
+    /// meaning [`ChannelIdExt::fetch_messages`] needs to reverse them to
+    /// honor its documented oldest-first order.
+    fn needs_reversal(&self) -> bool {
+        matches!(
+            self,
+            MessageQuery::Latest { .. } | MessageQuery::Before { .. }
+        )
+    }
+}
+
 #[async_trait::async_trait]
 pub trait ChannelExt {
     async fn server(&self, ctx: &impl CacheHttp) -> Result<Option<Server>>;
@@ -146,26 +258,45 @@ pub trait ChannelIdExt {
     where
         F: for<'a> FnOnce(&'a mut CreateMessage) -> &'a CreateMessage + Send;
 
+    /// Fetches a page of message history according to `query`, committing
+    /// every returned [`Message`] to the cache. Regardless of `query`, the
+    /// result is always sorted oldest to newest.
+    async fn fetch_messages(
+        &self,
+        ctx: &impl CacheHttp,
+        query: MessageQuery,
+    ) -> Result<Vec<Message>>;
+
     #[cfg(feature = "events")]
     fn start_typing(&self, ctx: &impl AsRefContext) -> TypingSession;
 }
 
 #[async_trait::async_trait]
 impl ChannelIdExt for ChannelId {
+    #[cfg_attr(feature = "metrics", tracing::instrument(skip(ctx)))]
     async fn channel(&self, ctx: &impl CacheHttp) -> Result<Channel> {
         #[cfg(feature = "cache")]
         if let Some(cache) = ctx.cache() {
             if let Some(channel) = cache.get_channel(*self).await {
+                #[cfg(feature = "metrics")]
+                metrics::counter!("robespierre_cache_hit_total", "entity" => "channel")
+                    .increment(1);
                 return Ok(channel);
             }
         }
 
-        Ok(ctx
-            .http()
-            .fetch_channel(*self)
-            .await?
-            .commit_to_cache(ctx)
-            .await)
+        #[cfg(feature = "metrics")]
+        metrics::counter!("robespierre_cache_miss_total", "entity" => "channel").increment(1);
+
+        #[cfg(feature = "metrics")]
+        let started_at = std::time::Instant::now();
+
+        let result = ctx.http().fetch_channel(*self).await;
+
+        #[cfg(feature = "metrics")]
+        record_http_metrics("fetch_channel", started_at, &result);
+
+        Ok(result?.commit_to_cache(ctx).await)
     }
 
     async fn server_id(&self, ctx: &impl CacheHttp) -> Result<Option<ServerId>> {
@@ -176,6 +307,7 @@ impl ChannelIdExt for ChannelId {
         self.channel(ctx).await?.server(ctx).await
     }
 
+    #[cfg_attr(feature = "metrics", tracing::instrument(skip(http, message)))]
     async fn send_message<F>(&self, http: &impl HasHttp, message: F) -> Result<Message>
     where
         F: for<'a> FnOnce(&'a mut CreateMessage) -> &'a CreateMessage + Send,
@@ -183,7 +315,18 @@ impl ChannelIdExt for ChannelId {
         let mut m = CreateMessage::default();
         message(&mut m);
 
-        Ok(http
+        // Held until this call returns, so a `Shutdown::trigger` racing with
+        // an in-flight send waits for it via `Shutdown::drain` instead of
+        // the events loop closing the socket out from under it. `HasHttp`
+        // implementors with no events loop attached (or the `events`
+        // feature disabled) have nothing to guard against, hence the `Option`.
+        #[cfg(feature = "events")]
+        let _guard = http.shutdown_guard();
+
+        #[cfg(feature = "metrics")]
+        let started_at = std::time::Instant::now();
+
+        let result = http
             .get_http()
             .send_message(
                 *self,
@@ -192,7 +335,41 @@ impl ChannelIdExt for ChannelId {
                 m.attachments,
                 m.replies,
             )
-            .await?)
+            .await;
+
+        #[cfg(feature = "metrics")]
+        record_http_metrics("send_message", started_at, &result);
+
+        Ok(result?)
+    }
+
+    #[cfg_attr(feature = "metrics", tracing::instrument(skip(ctx)))]
+    async fn fetch_messages(
+        &self,
+        ctx: &impl CacheHttp,
+        query: MessageQuery,
+    ) -> Result<Vec<Message>> {
+        let needs_reversal = query.needs_reversal();
+        let (before, after, sort, nearby, limit) = query.to_http_params();
+
+        let mut messages = ctx
+            .get_http()
+            .fetch_messages(*self, before, after, sort, nearby, limit)
+            .await?;
+
+        if needs_reversal {
+            // `sort: "Latest"` returns newest-first; normalize to
+            // oldest-first like every other variant. `Around`'s `nearby`
+            // query already comes back oldest-first with the pivot centered.
+            messages.reverse();
+        }
+
+        let mut committed = Vec::with_capacity(messages.len());
+        for message in messages.drain(..) {
+            committed.push(message.commit_to_cache(ctx).await);
+        }
+
+        Ok(committed)
     }
 
     #[cfg(feature = "events")]
@@ -208,20 +385,29 @@ pub trait ServerIdExt {
 
 #[async_trait::async_trait]
 impl ServerIdExt for ServerId {
+    #[cfg_attr(feature = "metrics", tracing::instrument(skip(ctx)))]
     async fn server(&self, ctx: &impl CacheHttp) -> Result<Server> {
         #[cfg(feature = "cache")]
         if let Some(cache) = ctx.cache() {
             if let Some(server) = cache.get_server(*self).await {
+                #[cfg(feature = "metrics")]
+                metrics::counter!("robespierre_cache_hit_total", "entity" => "server").increment(1);
                 return Ok(server);
             }
         }
 
-        Ok(ctx
-            .http()
-            .fetch_server(*self)
-            .await?
-            .commit_to_cache(ctx)
-            .await)
+        #[cfg(feature = "metrics")]
+        metrics::counter!("robespierre_cache_miss_total", "entity" => "server").increment(1);
+
+        #[cfg(feature = "metrics")]
+        let started_at = std::time::Instant::now();
+
+        let result = ctx.http().fetch_server(*self).await;
+
+        #[cfg(feature = "metrics")]
+        record_http_metrics("fetch_server", started_at, &result);
+
+        Ok(result?.commit_to_cache(ctx).await)
     }
 }
 
@@ -232,19 +418,79 @@ pub trait UserIdExt {
 
 #[async_trait::async_trait]
 impl UserIdExt for UserId {
+    #[cfg_attr(feature = "metrics", tracing::instrument(skip(ctx)))]
     async fn user(&self, ctx: &impl CacheHttp) -> Result<User> {
         #[cfg(feature = "cache")]
         if let Some(cache) = ctx.cache() {
             if let Some(user) = cache.get_user(*self).await {
+                #[cfg(feature = "metrics")]
+                metrics::counter!("robespierre_cache_hit_total", "entity" => "user").increment(1);
                 return Ok(user);
             }
         }
 
-        Ok(ctx
-            .http()
-            .fetch_user(*self)
-            .await?
-            .commit_to_cache(ctx)
-            .await)
+        #[cfg(feature = "metrics")]
+        metrics::counter!("robespierre_cache_miss_total", "entity" => "user").increment(1);
+
+        #[cfg(feature = "metrics")]
+        let started_at = std::time::Instant::now();
+
+        let result = ctx.http().fetch_user(*self).await;
+
+        #[cfg(feature = "metrics")]
+        record_http_metrics("fetch_user", started_at, &result);
+
+        Ok(result?.commit_to_cache(ctx).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limit_clamps_to_server_maximum() {
+        assert_eq!(MessageQuery::Latest { limit: 10 }.limit(), 10);
+        assert_eq!(
+            MessageQuery::Latest {
+                limit: MAX_FETCH_MESSAGES_LIMIT * 10
+            }
+            .limit(),
+            MAX_FETCH_MESSAGES_LIMIT
+        );
+    }
+
+    #[test]
+    fn only_latest_and_before_need_reversal() {
+        let id = MessageId::default();
+
+        assert!(MessageQuery::Latest { limit: 10 }.needs_reversal());
+        assert!(MessageQuery::Before { id, limit: 10 }.needs_reversal());
+        assert!(!MessageQuery::After { id, limit: 10 }.needs_reversal());
+        assert!(!MessageQuery::Around { id, limit: 10 }.needs_reversal());
+        assert!(!MessageQuery::Between {
+            start: id,
+            end: id,
+            limit: 10
+        }
+        .needs_reversal());
+    }
+
+    #[test]
+    fn between_maps_to_exclusive_before_after_params() {
+        let start = MessageId::default();
+        let end = MessageId::default();
+
+        let (before, after, sort, nearby, _limit) = MessageQuery::Between {
+            start,
+            end,
+            limit: 10,
+        }
+        .to_http_params();
+
+        assert_eq!(before, Some(end));
+        assert_eq!(after, Some(start));
+        assert_eq!(sort, "Oldest");
+        assert_eq!(nearby, None);
     }
 }
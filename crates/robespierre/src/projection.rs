@@ -0,0 +1,58 @@
+//! Translation between Revolt's gateway protocol and a network-neutral
+//! message model, so this crate can back bridges to other chat networks
+//! (IRC, XMPP, Matrix, ...) the way other Revolt bridge projects do.
+
+use robespierre_models::{
+    events::{ClientToServerEvent, ServerToClientEvent},
+    id::{ChannelId, ServerId, UserId},
+};
+
+/// The bridge-facing identifier for a Revolt channel, optionally scoped to
+/// the server it belongs to (a DM or group has no server).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Room {
+    pub server: Option<ServerId>,
+    pub channel: ChannelId,
+}
+
+/// The bridge-facing identifier for a Revolt user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Identity {
+    pub user: UserId,
+}
+
+/// A gateway event translated into a representation that assumes nothing
+/// Revolt-specific, so a [`Projection`] implementor can re-emit it onto
+/// another network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProjectedEvent {
+    /// `identity` posted `body` in `room`.
+    Message {
+        room: Room,
+        identity: Identity,
+        body: String,
+    },
+    /// `identity` joined `room`.
+    Join { room: Room, identity: Identity },
+    /// `identity` left `room`.
+    Leave { room: Room, identity: Identity },
+}
+
+/// Translates between [`ServerToClientEvent`]/[`ClientToServerEvent`] and
+/// [`ProjectedEvent`].
+///
+/// Implementors own the mapping from Revolt channels/servers onto the
+/// target network's idea of a "room" and from Revolt users onto its idea of
+/// an identity. The mention conversion in `mention::plain` (`<@id>` ↔
+/// `@name`) is shipped as the first concrete piece of that mapping; it
+/// translates user mentions only and leaves markdown in the body as it is.
+pub trait Projection {
+    /// Translates a gateway event into zero or more neutral events. Most
+    /// `ServerToClientEvent` variants have no bridge-relevant counterpart
+    /// and translate to an empty `Vec`.
+    fn from_revolt(&self, event: &ServerToClientEvent) -> Vec<ProjectedEvent>;
+
+    /// Translates a neutral event back into a gateway event to send, if the
+    /// action it describes has a Revolt equivalent.
+    fn to_revolt(&self, event: ProjectedEvent) -> Option<ClientToServerEvent>;
+}
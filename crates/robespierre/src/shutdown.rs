@@ -0,0 +1,12 @@
+//! Cooperative shutdown for the events loop and anything else that holds a
+//! live connection open (typing sessions, in-flight sends) and needs a
+//! chance to wind down cleanly instead of being dropped mid-flight.
+//!
+//! The canonical definition lives in `robespierre_events`, since the events
+//! loop is what actually races [`Shutdown::triggered`] against its next read
+//! from the socket and drains every live `TypingSession` and
+//! [`ShutdownGuard`] before returning — re-exported here so callers that
+//! only depend on `robespierre` (not `robespierre_events` directly) don't
+//! need an extra `use`.
+
+pub use robespierre_events::shutdown::{Shutdown, ShutdownGuard};